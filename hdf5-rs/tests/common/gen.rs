@@ -1,11 +1,15 @@
+use std::env;
 use std::fmt;
+use std::fs;
 use std::iter;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use h5::types::{FixedAscii, FixedUnicode, VarLenArray, VarLenAscii, VarLenUnicode};
-use h5::H5Type;
+use h5::{File, H5Type};
 use hdf5_types::Array;
 
-use ndarray::ArrayD;
+use ndarray::{ArrayD, Axis, Slice};
 use rand::distributions::{Alphanumeric, Uniform};
 use rand::prelude::{Rng, SliceRandom};
 
@@ -37,6 +41,108 @@ macro_rules! impl_gen_primitive {
 
 impl_gen_primitive!(usize, isize, u8, u16, u32, u64, i8, i16, i32, i64, bool, f32, f64);
 
+/// Minimizes a value that reproduces a roundtrip failure, alongside the
+/// `Gen` impl it shrinks. Each yielded value should be "smaller" in some
+/// sense than `self`; `check_roundtrip` repeatedly applies this until no
+/// shrink reproduces the failure any more.
+pub trait Shrink: Sized {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>>;
+}
+
+macro_rules! impl_shrink_unsigned {
+    ($ty:ty) => {
+        impl Shrink for $ty {
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                let v = *self;
+                let mut vals: Vec<$ty> = Vec::new();
+                if v != 0 {
+                    vals.push(0);
+                    let mut half = v / 2;
+                    while half != 0 && !vals.contains(&half) {
+                        vals.push(half);
+                        half /= 2;
+                    }
+                    vals.push(v - 1);
+                }
+                vals.sort();
+                vals.dedup();
+                Box::new(vals.into_iter())
+            }
+        }
+    };
+    ($ty:ty, $($tys:ty),+) => {
+        impl_shrink_unsigned!($ty);
+        impl_shrink_unsigned!($($tys),*);
+    };
+}
+
+impl_shrink_unsigned!(usize, u8, u16, u32, u64);
+
+macro_rules! impl_shrink_signed {
+    ($ty:ty) => {
+        impl Shrink for $ty {
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                let v = *self;
+                let mut vals: Vec<$ty> = Vec::new();
+                if v != 0 {
+                    vals.push(0);
+                    let mut half = v / 2;
+                    while half != 0 && !vals.contains(&half) {
+                        vals.push(half);
+                        half /= 2;
+                    }
+                    if v < 0 {
+                        if let Some(neg) = v.checked_neg() {
+                            vals.push(neg);
+                        }
+                    }
+                    vals.push(if v > 0 { v - 1 } else { v + 1 });
+                }
+                vals.sort();
+                vals.dedup();
+                Box::new(vals.into_iter())
+            }
+        }
+    };
+    ($ty:ty, $($tys:ty),+) => {
+        impl_shrink_signed!($ty);
+        impl_shrink_signed!($($tys),*);
+    };
+}
+
+impl_shrink_signed!(isize, i8, i16, i32, i64);
+
+macro_rules! impl_shrink_float {
+    ($ty:ty) => {
+        impl Shrink for $ty {
+            fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+                let v = *self;
+                if v == 0.0 || !v.is_finite() {
+                    Box::new(iter::empty())
+                } else {
+                    Box::new(vec![0.0, v / 2.0].into_iter())
+                }
+            }
+        }
+    };
+    ($ty:ty, $($tys:ty),+) => {
+        impl_shrink_float!($ty);
+        impl_shrink_float!($($tys),*);
+    };
+}
+
+impl_shrink_float!(f32, f64);
+
+impl Shrink for bool {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        if *self {
+            Box::new(iter::once(false))
+        } else {
+            Box::new(iter::empty())
+        }
+    }
+}
+
 pub fn gen_vec<R: Rng + ?Sized, T: Gen>(rng: &mut R, size: usize) -> Vec<T> {
     iter::repeat(()).map(|_| T::gen(rng)).take(size).collect()
 }
@@ -52,6 +158,19 @@ where
     ArrayD::from_shape_vec(shape, vec).unwrap()
 }
 
+impl<T: Gen + Shrink + Clone> Shrink for ArrayD<T> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let mut smaller = Vec::new();
+        for axis in 0..self.ndim() {
+            let len = self.len_of(Axis(axis));
+            if len > 0 {
+                smaller.push(self.slice_axis(Axis(axis), Slice::from(0..len - 1)).to_owned());
+            }
+        }
+        Box::new(smaller.into_iter())
+    }
+}
+
 impl<A: Array<Item = u8>> Gen for FixedAscii<A> {
     fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let len = rng.sample(Uniform::new_inclusive(0, A::capacity()));
@@ -64,6 +183,20 @@ impl<A: Array<Item = u8>> Gen for FixedAscii<A> {
     }
 }
 
+impl<A: Array<Item = u8>> Shrink for FixedAscii<A> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let s = self.as_str();
+        let mut vals = Vec::new();
+        if !s.is_empty() {
+            vals.push(String::new());
+            if s.len() > 1 {
+                vals.push(s[..s.len() / 2].to_owned());
+            }
+        }
+        Box::new(vals.into_iter().map(|v| unsafe { FixedAscii::from_ascii_unchecked(v.as_bytes()) }))
+    }
+}
+
 impl<A: Array<Item = u8>> Gen for FixedUnicode<A> {
     fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let len = rng.sample(Uniform::new_inclusive(0, A::capacity()));
@@ -81,6 +214,21 @@ impl<A: Array<Item = u8>> Gen for FixedUnicode<A> {
     }
 }
 
+impl<A: Array<Item = u8>> Shrink for FixedUnicode<A> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let s = self.as_str();
+        let mut vals = Vec::new();
+        if !s.is_empty() {
+            vals.push(String::new());
+            if s.chars().count() > 1 {
+                let half: String = s.chars().take(s.chars().count() / 2).collect();
+                vals.push(half);
+            }
+        }
+        Box::new(vals.into_iter().map(|v| unsafe { FixedUnicode::from_str_unchecked(v) }))
+    }
+}
+
 impl Gen for VarLenAscii {
     fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let len = rng.sample(Uniform::new_inclusive(0, 8));
@@ -93,6 +241,20 @@ impl Gen for VarLenAscii {
     }
 }
 
+impl Shrink for VarLenAscii {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let s = self.as_str();
+        let mut vals = Vec::new();
+        if !s.is_empty() {
+            vals.push(String::new());
+            if s.len() > 1 {
+                vals.push(s[..s.len() / 2].to_owned());
+            }
+        }
+        Box::new(vals.into_iter().map(|v| unsafe { VarLenAscii::from_ascii_unchecked(v.as_bytes()) }))
+    }
+}
+
 impl Gen for VarLenUnicode {
     fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let len = rng.sample(Uniform::new_inclusive(0, 8));
@@ -107,6 +269,21 @@ impl Gen for VarLenUnicode {
     }
 }
 
+impl Shrink for VarLenUnicode {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let s = self.as_str();
+        let mut vals = Vec::new();
+        if !s.is_empty() {
+            vals.push(String::new());
+            if s.chars().count() > 1 {
+                let half: String = s.chars().take(s.chars().count() / 2).collect();
+                vals.push(half);
+            }
+        }
+        Box::new(vals.into_iter().map(|v| unsafe { VarLenUnicode::from_str_unchecked(v) }))
+    }
+}
+
 impl<T: Gen + Copy> Gen for VarLenArray<T> {
     fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let len = rng.sample(Uniform::new_inclusive(0, 8));
@@ -118,6 +295,21 @@ impl<T: Gen + Copy> Gen for VarLenArray<T> {
     }
 }
 
+impl<T: Gen + Shrink + Copy> Shrink for VarLenArray<T> {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let slice = self.as_slice().to_vec();
+        let mut vals = Vec::new();
+        if !slice.is_empty() {
+            vals.push(Vec::new());
+            if slice.len() > 1 {
+                vals.push(slice[..slice.len() / 2].to_vec());
+                vals.push(slice[..slice.len() - 1].to_vec());
+            }
+        }
+        Box::new(vals.into_iter().map(|v| VarLenArray::from_slice(&v)))
+    }
+}
+
 #[derive(H5Type, Clone, Copy, Debug, PartialEq)]
 #[repr(i16)]
 pub enum Enum {
@@ -131,6 +323,15 @@ impl Gen for Enum {
     }
 }
 
+impl Shrink for Enum {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        match *self {
+            Enum::X => Box::new(iter::empty()),
+            Enum::Y => Box::new(iter::once(Enum::X)),
+        }
+    }
+}
+
 #[derive(H5Type, Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub struct TupleStruct(bool, Enum);
@@ -141,6 +342,16 @@ impl Gen for TupleStruct {
     }
 }
 
+impl Shrink for TupleStruct {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let TupleStruct(a, b) = *self;
+        let mut vals = Vec::new();
+        vals.extend(a.shrink().map(|a| TupleStruct(a, b)));
+        vals.extend(b.shrink().map(|b| TupleStruct(a, b)));
+        Box::new(vals.into_iter())
+    }
+}
+
 #[derive(H5Type, Clone, Debug, PartialEq)]
 #[repr(C)]
 pub struct FixedStruct {
@@ -161,6 +372,27 @@ impl Gen for FixedStruct {
     }
 }
 
+impl Shrink for FixedStruct {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let mut vals = Vec::new();
+        let (t0, t1, t2) = self.tuple;
+        vals.extend(self.fa.shrink().map(|fa| FixedStruct { fa, ..self.clone() }));
+        vals.extend(self.fu.shrink().map(|fu| FixedStruct { fu, ..self.clone() }));
+        vals.extend(t0.shrink().map(|t0| FixedStruct { tuple: (t0, t1, t2), ..self.clone() }));
+        vals.extend(t1.shrink().map(|t1| FixedStruct { tuple: (t0, t1, t2), ..self.clone() }));
+        vals.extend(t2.shrink().map(|t2| FixedStruct { tuple: (t0, t1, t2), ..self.clone() }));
+        vals.extend(self.array[0].shrink().map(|a0| {
+            let array = [a0, self.array[1]];
+            FixedStruct { array, ..self.clone() }
+        }));
+        vals.extend(self.array[1].shrink().map(|a1| {
+            let array = [self.array[0], a1];
+            FixedStruct { array, ..self.clone() }
+        }));
+        Box::new(vals.into_iter())
+    }
+}
+
 #[derive(H5Type, Clone, Debug, PartialEq)]
 #[repr(C)]
 pub struct VarLenStruct {
@@ -174,3 +406,49 @@ impl Gen for VarLenStruct {
         VarLenStruct { va: Gen::gen(rng), vu: Gen::gen(rng), vla: Gen::gen(rng) }
     }
 }
+
+impl Shrink for VarLenStruct {
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let mut vals = Vec::new();
+        vals.extend(self.va.shrink().map(|va| VarLenStruct { va, ..self.clone() }));
+        vals.extend(self.vu.shrink().map(|vu| VarLenStruct { vu, ..self.clone() }));
+        vals.extend(self.vla.shrink().map(|vla| VarLenStruct { vla, ..self.clone() }));
+        Box::new(vals.into_iter())
+    }
+}
+
+static ROUNDTRIP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes `value` to a scalar dataset in a throwaway file and reads it back,
+/// to exercise the roundtrip through HDF5's type conversion machinery.
+fn roundtrip<T: H5Type + Clone>(value: &T) -> T {
+    let n = ROUNDTRIP_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let path = env::temp_dir().join(format!("hdf5-rust-roundtrip-{}-{}.h5", process::id(), n));
+    let file = File::create(&path).unwrap();
+    let ds = file.new_dataset::<T>().shape(()).create("x").unwrap();
+    ds.write_scalar(value).unwrap();
+    let result = ds.read_scalar().unwrap();
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Generates `tries` random values of `T` and checks that each one survives
+/// a write/read roundtrip. On the first mismatch, repeatedly shrinks the
+/// failing value and re-checks until no further shrink reproduces the
+/// failure, then panics reporting the smallest reproducing value found.
+pub fn check_roundtrip<T, R>(rng: &mut R, tries: usize)
+where
+    T: Gen + Shrink + H5Type + Clone + PartialEq,
+    R: Rng + ?Sized,
+{
+    for _ in 0..tries {
+        let value = T::gen(rng);
+        if roundtrip(&value) != value {
+            let mut smallest = value;
+            while let Some(smaller) = smallest.shrink().find(|s| roundtrip(s) != *s) {
+                smallest = smaller;
+            }
+            panic!("roundtrip mismatch, shrunk to smallest reproducing value: {:?}", smallest);
+        }
+    }
+}
@@ -1,14 +1,22 @@
+extern crate ndarray;
+
+use self::ndarray::{Dimension as NdarrayDimension, IxDyn};
+
 use ffi::h5::hsize_t;
 use ffi::h5i::{H5I_DATASPACE, hid_t};
-use ffi::h5s::{H5S_UNLIMITED, H5Sget_simple_extent_dims, H5Sget_simple_extent_ndims,
-               H5Screate_simple};
+use ffi::h5s::{H5S_UNLIMITED, H5S_SCALAR, H5S_NULL, H5Sget_simple_extent_dims,
+               H5Sget_simple_extent_ndims, H5Sget_simple_extent_type, H5Screate,
+               H5Screate_simple, H5Sselect_all, H5Sselect_none, H5Sselect_elements,
+               H5Sselect_hyperslab, H5Sget_select_npoints, H5Sget_select_bounds,
+               H5S_SELECT_SET, H5S_SELECT_OR, H5S_SELECT_AND, H5S_SELECT_XOR,
+               H5S_SELECT_NOTA, H5S_SELECT_NOTB};
 
 use error::Result;
 use handle::{Handle, ID, get_id_type};
 use object::Object;
 
 use std::ptr;
-use libc::c_int;
+use libc::{c_int, size_t};
 
 pub type Ix = usize;
 
@@ -17,8 +25,7 @@ pub trait Dimension {
     fn dims(&self) -> Vec<Ix>;
 
     fn size(&self) -> Ix {
-        let dims = self.dims();
-        if dims.is_empty() { 0 } else { dims.iter().fold(1, |acc, &el| acc * el) }
+        self.dims().iter().fold(1, |acc, &el| acc * el)
     }
 }
 
@@ -42,14 +49,51 @@ impl Dimension for Ix {
     fn dims(&self) -> Vec<Ix> { vec![*self] }
 }
 
-impl Dimension for (Ix,) {
-    fn ndim(&self) -> usize { 1 }
-    fn dims(&self) -> Vec<Ix> { vec![self.0] }
+macro_rules! impl_dimension_tuple {
+    (@ix $idx:tt) => { Ix };
+    ($n:expr; $($idx:tt),+) => {
+        impl Dimension for ($(impl_dimension_tuple!(@ix $idx)),+,) {
+            fn ndim(&self) -> usize { $n }
+            fn dims(&self) -> Vec<Ix> { vec![$(self.$idx),+] }
+        }
+    };
+}
+
+impl_dimension_tuple!(1; 0);
+impl_dimension_tuple!(2; 0, 1);
+impl_dimension_tuple!(3; 0, 1, 2);
+impl_dimension_tuple!(4; 0, 1, 2, 3);
+impl_dimension_tuple!(5; 0, 1, 2, 3, 4);
+impl_dimension_tuple!(6; 0, 1, 2, 3, 4, 5);
+impl_dimension_tuple!(7; 0, 1, 2, 3, 4, 5, 6);
+impl_dimension_tuple!(8; 0, 1, 2, 3, 4, 5, 6, 7);
+
+macro_rules! impl_dimension_array {
+    ($n:expr; $($idx:tt),+) => {
+        impl Dimension for [Ix; $n] {
+            fn ndim(&self) -> usize { $n }
+            fn dims(&self) -> Vec<Ix> { vec![$(self[$idx]),+] }
+        }
+    };
 }
 
-impl Dimension for (Ix, Ix) {
-    fn ndim(&self) -> usize { 2 }
-    fn dims(&self) -> Vec<Ix> { vec![self.0, self.1] }
+impl_dimension_array!(1; 0);
+impl_dimension_array!(2; 0, 1);
+impl_dimension_array!(3; 0, 1, 2);
+impl_dimension_array!(4; 0, 1, 2, 3);
+impl_dimension_array!(5; 0, 1, 2, 3, 4);
+impl_dimension_array!(6; 0, 1, 2, 3, 4, 5);
+impl_dimension_array!(7; 0, 1, 2, 3, 4, 5, 6);
+impl_dimension_array!(8; 0, 1, 2, 3, 4, 5, 6, 7);
+
+impl Dimension for IxDyn {
+    fn ndim(&self) -> usize {
+        NdarrayDimension::ndim(self)
+    }
+
+    fn dims(&self) -> Vec<Ix> {
+        self.slice().to_vec()
+    }
 }
 
 pub struct Dataspace {
@@ -57,16 +101,70 @@ pub struct Dataspace {
 }
 
 impl Dataspace {
+    /// A simple dataspace with a fixed extent equal to `d` (its maximum
+    /// dimensions equal its current extent, so it cannot be resized).
     pub fn new<D: Dimension>(d: D) -> Result<Dataspace> {
-        let rank = d.ndim();
-        let mut dims: Vec<hsize_t> = vec![];
-        let mut max_dims: Vec<hsize_t> = vec![];
-        for dim in d.dims().iter() {
-            dims.push(*dim as hsize_t);
-            max_dims.push(H5S_UNLIMITED);
+        Dataspace::fixed(d)
+    }
+
+    /// A simple dataspace with a fixed extent equal to `d`.
+    pub fn fixed<D: Dimension>(d: D) -> Result<Dataspace> {
+        let dims: Vec<hsize_t> = d.dims().iter().map(|&x| x as hsize_t).collect();
+        let rank = dims.len();
+        Dataspace::from_id(h5try!(H5Screate_simple(rank as c_int, dims.as_ptr(), dims.as_ptr())))
+    }
+
+    /// A simple dataspace with current extent `dims` and maximum extent
+    /// `max_dims`; `None` marks an axis as unlimited (`H5S_UNLIMITED`).
+    pub fn resizable<D: Dimension>(dims: D, max_dims: &[Option<usize>]) -> Result<Dataspace> {
+        let dims: Vec<hsize_t> = dims.dims().iter().map(|&x| x as hsize_t).collect();
+        let rank = dims.len();
+        if max_dims.len() != rank {
+            return Err(From::from(format!(
+                "max_dims has {} entries, expected {} (one per dimension)",
+                max_dims.len(), rank)));
         }
+        let max_dims: Vec<hsize_t> = max_dims.iter()
+            .map(|&m| m.map(|x| x as hsize_t).unwrap_or(H5S_UNLIMITED)).collect();
         Dataspace::from_id(h5try!(H5Screate_simple(rank as c_int, dims.as_ptr(),
-                                                   max_dims.as_ptr())))
+                                                    max_dims.as_ptr())))
+    }
+
+    /// A scalar dataspace: rank 0, holding exactly one element.
+    pub fn scalar() -> Result<Dataspace> {
+        Dataspace::from_id(h5try!(H5Screate(H5S_SCALAR)))
+    }
+
+    /// A null dataspace: holds no elements and no selection is possible.
+    pub fn null() -> Result<Dataspace> {
+        Dataspace::from_id(h5try!(H5Screate(H5S_NULL)))
+    }
+
+    /// The maximum dimensions of the extent; `None` marks an axis as
+    /// unlimited, read back from `H5Sget_simple_extent_dims`.
+    pub fn maxdims(&self) -> Vec<Option<usize>> {
+        let ndim = self.ndim();
+        if ndim > 0 {
+            let mut max_dims: Vec<hsize_t> = Vec::with_capacity(ndim);
+            unsafe { max_dims.set_len(ndim); }
+            if h5call!(H5Sget_simple_extent_dims(self.id(), ptr::null_mut(),
+                                                 max_dims.as_mut_ptr())).is_ok() {
+                return max_dims.iter().map(|&x| {
+                    if x == H5S_UNLIMITED { None } else { Some(x as usize) }
+                }).collect();
+            }
+        }
+        vec![]
+    }
+
+    /// Whether this is a scalar dataspace, via `H5Sget_simple_extent_type`.
+    pub fn is_scalar(&self) -> bool {
+        h5call!(H5Sget_simple_extent_type(self.id())).map(|t| t == H5S_SCALAR).unwrap_or(false)
+    }
+
+    /// Whether this is a null dataspace, via `H5Sget_simple_extent_type`.
+    pub fn is_null(&self) -> bool {
+        h5call!(H5Sget_simple_extent_type(self.id())).map(|t| t == H5S_NULL).unwrap_or(false)
     }
 }
 
@@ -87,6 +185,184 @@ impl Dimension for Dataspace {
         }
         vec![]
     }
+
+    /// A null dataspace holds no elements at all, unlike a scalar (rank-0)
+    /// dataspace, which holds exactly one -- so it must be special-cased
+    /// here rather than falling through to the empty-product default.
+    fn size(&self) -> Ix {
+        if self.is_null() { 0 } else { self.dims().iter().fold(1, |acc, &el| acc * el) }
+    }
+}
+
+/// The operator used to combine a new hyperslab selection with whatever is
+/// already selected on a `Dataspace` (mirrors `H5S_seloper_t`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectOp {
+    Set,
+    Or,
+    And,
+    Xor,
+    NotA,
+    NotB,
+}
+
+impl SelectOp {
+    fn to_raw(self) -> ::libc::c_int {
+        match self {
+            SelectOp::Set  => H5S_SELECT_SET,
+            SelectOp::Or   => H5S_SELECT_OR,
+            SelectOp::And  => H5S_SELECT_AND,
+            SelectOp::Xor  => H5S_SELECT_XOR,
+            SelectOp::NotA => H5S_SELECT_NOTA,
+            SelectOp::NotB => H5S_SELECT_NOTB,
+        }
+    }
+}
+
+/// A single contiguous run of elements produced by `Hyperslab::spans()`.
+///
+/// `start` gives the coordinates of the first element of the run; the run
+/// extends `len` elements along the fastest-varying (last) dimension.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub start: Vec<Ix>,
+    pub len: usize,
+}
+
+/// Iterator over the contiguous spans of a regular hyperslab, see
+/// `Hyperslab::spans()`.
+pub struct HyperslabSpans {
+    spans: ::std::vec::IntoIter<Span>,
+}
+
+impl Iterator for HyperslabSpans {
+    type Item = Span;
+
+    fn next(&mut self) -> Option<Span> {
+        self.spans.next()
+    }
+}
+
+/// A regular hyperslab selection: `count` blocks of `block` elements each,
+/// spaced `stride` apart, starting at `start` -- one entry per dimension.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperslab {
+    pub start: Vec<Ix>,
+    pub stride: Vec<Ix>,
+    pub count: Vec<Ix>,
+    pub block: Vec<Ix>,
+}
+
+impl Hyperslab {
+    /// A hyperslab of single elements (`stride` and `block` both all-ones).
+    pub fn new(start: Vec<Ix>, count: Vec<Ix>) -> Hyperslab {
+        let ndim = start.len();
+        Hyperslab { start: start, stride: vec![1; ndim], count: count, block: vec![1; ndim] }
+    }
+
+    /// A hyperslab with explicit `stride` and `block` for every dimension.
+    pub fn with_stride_block(start: Vec<Ix>, stride: Vec<Ix>, count: Vec<Ix>,
+                              block: Vec<Ix>) -> Hyperslab {
+        Hyperslab { start: start, stride: stride, count: count, block: block }
+    }
+
+    pub fn ndim(&self) -> usize {
+        self.start.len()
+    }
+
+    /// Yields the contiguous spans covered by this hyperslab, merging the
+    /// last dimension's blocks into a single run wherever `stride == block`
+    /// makes them adjacent in memory.
+    pub fn spans(&self) -> HyperslabSpans {
+        let ndim = self.ndim();
+        if ndim == 0 {
+            return HyperslabSpans { spans: vec![Span { start: vec![], len: 1 }].into_iter() };
+        }
+
+        let last = ndim - 1;
+        let mut prefixes: Vec<Vec<Ix>> = vec![vec![]];
+        for d in 0..last {
+            let mut next = Vec::new();
+            for prefix in &prefixes {
+                for i in 0..self.count[d] {
+                    for j in 0..self.block[d] {
+                        let mut coord = prefix.clone();
+                        coord.push(self.start[d] + i * self.stride[d] + j);
+                        next.push(coord);
+                    }
+                }
+            }
+            prefixes = next;
+        }
+
+        let contiguous = self.stride[last] == self.block[last];
+        let mut spans = Vec::new();
+        for prefix in prefixes {
+            if contiguous {
+                let mut start = prefix.clone();
+                start.push(self.start[last]);
+                spans.push(Span { start: start, len: self.count[last] * self.block[last] });
+            } else {
+                for i in 0..self.count[last] {
+                    let mut start = prefix.clone();
+                    start.push(self.start[last] + i * self.stride[last]);
+                    spans.push(Span { start: start, len: self.block[last] });
+                }
+            }
+        }
+        HyperslabSpans { spans: spans.into_iter() }
+    }
+}
+
+impl Dataspace {
+    /// Combine the current selection with a hyperslab via `H5Sselect_hyperslab`.
+    pub fn select_hyperslab(&self, op: SelectOp, slab: &Hyperslab) -> Result<()> {
+        let start: Vec<hsize_t> = slab.start.iter().map(|&x| x as hsize_t).collect();
+        let stride: Vec<hsize_t> = slab.stride.iter().map(|&x| x as hsize_t).collect();
+        let count: Vec<hsize_t> = slab.count.iter().map(|&x| x as hsize_t).collect();
+        let block: Vec<hsize_t> = slab.block.iter().map(|&x| x as hsize_t).collect();
+        h5call!(H5Sselect_hyperslab(self.id(), op.to_raw(), start.as_ptr(), stride.as_ptr(),
+                                     count.as_ptr(), block.as_ptr())).and(Ok(()))
+    }
+
+    /// Select a scattered set of points via `H5Sselect_elements`; each
+    /// element of `coords` is one point, given as its coordinate in every
+    /// dimension.
+    pub fn select_elements(&self, coords: &[Vec<Ix>]) -> Result<()> {
+        let mut flat: Vec<hsize_t> = Vec::with_capacity(coords.len() * self.ndim());
+        for point in coords {
+            flat.extend(point.iter().map(|&x| x as hsize_t));
+        }
+        h5call!(H5Sselect_elements(self.id(), H5S_SELECT_SET, coords.len() as size_t,
+                                    flat.as_ptr())).and(Ok(()))
+    }
+
+    /// Select the whole extent via `H5Sselect_all`.
+    pub fn select_all(&self) -> Result<()> {
+        h5call!(H5Sselect_all(self.id())).and(Ok(()))
+    }
+
+    /// Clear the selection via `H5Sselect_none`.
+    pub fn select_none(&self) -> Result<()> {
+        h5call!(H5Sselect_none(self.id())).and(Ok(()))
+    }
+
+    /// Number of points covered by the current selection, via
+    /// `H5Sget_select_npoints`.
+    pub fn selected_npoints(&self) -> usize {
+        h5call!(H5Sget_select_npoints(self.id())).unwrap_or(0) as usize
+    }
+
+    /// The bounding box `(low, high)` of the current selection, via
+    /// `H5Sget_select_bounds`; `high` is inclusive, as returned by HDF5.
+    pub fn bounds(&self) -> Result<(Vec<Ix>, Vec<Ix>)> {
+        let ndim = self.ndim();
+        let mut start: Vec<hsize_t> = vec![0; ndim];
+        let mut end: Vec<hsize_t> = vec![0; ndim];
+        h5try!(H5Sget_select_bounds(self.id(), start.as_mut_ptr(), end.as_mut_ptr()));
+        Ok((start.iter().map(|&x| x as usize).collect(),
+            end.iter().map(|&x| x as usize).collect()))
+    }
 }
 
 impl ID for Dataspace {
@@ -106,14 +382,14 @@ impl Object for Dataspace {}
 
 #[cfg(test)]
 mod tests {
-    use super::{Dimension, Ix};
+    use super::{Dimension, Hyperslab, Ix, Span};
 
     #[test]
     pub fn test_dimension() {
         fn f<D: Dimension>(d: D) -> (usize, Vec<Ix>, Ix) { (d.ndim(), d.dims(), d.size()) }
 
-        assert_eq!(f(()), (0, vec![], 0));
-        assert_eq!(f(&()), (0, vec![], 0));
+        assert_eq!(f(()), (0, vec![], 1));
+        assert_eq!(f(&()), (0, vec![], 1));
         assert_eq!(f(2), (1, vec![2], 2));
         assert_eq!(f(&3), (1, vec![3], 3));
         assert_eq!(f((4,)), (1, vec![4], 4));
@@ -122,5 +398,67 @@ mod tests {
         assert_eq!(f(&(3, 4)), (2, vec![3, 4], 12));
         assert_eq!(f(vec![2, 3]), (2, vec![2, 3], 6));
         assert_eq!(f(&vec![4, 5]), (2, vec![4, 5], 20));
+        assert_eq!(f((1, 2, 3)), (3, vec![1, 2, 3], 6));
+        assert_eq!(f((1, 2, 3, 4, 5, 6, 7, 8)), (8, vec![1, 2, 3, 4, 5, 6, 7, 8], 40320));
+        assert_eq!(f([2, 3, 4]), (3, vec![2, 3, 4], 24));
+    }
+
+    #[test]
+    pub fn test_dimension_ndarray() {
+        use super::ndarray::IxDyn;
+
+        let shape = IxDyn(&[2, 3, 4]);
+        assert_eq!(shape.ndim(), 3);
+        assert_eq!(shape.dims(), vec![2, 3, 4]);
+        assert_eq!(shape.size(), 24);
+    }
+
+    #[test]
+    pub fn test_dataspace_extent_kinds() {
+        use super::Dataspace;
+
+        let fixed = Dataspace::fixed(vec![3, 4]).unwrap();
+        assert_eq!(fixed.maxdims(), vec![Some(3), Some(4)]);
+
+        let resizable = Dataspace::resizable(vec![3, 4], &[Some(3), None]).unwrap();
+        assert_eq!(resizable.maxdims(), vec![Some(3), None]);
+
+        let scalar = Dataspace::scalar().unwrap();
+        assert!(scalar.is_scalar());
+        assert!(!scalar.is_null());
+
+        let null = Dataspace::null().unwrap();
+        assert!(null.is_null());
+        assert!(!null.is_scalar());
+        assert_eq!(null.size(), 0);
+        assert_eq!(scalar.size(), 1);
+    }
+
+    #[test]
+    pub fn test_dataspace_resizable_rank_mismatch() {
+        use super::Dataspace;
+
+        assert!(Dataspace::resizable(vec![3, 4], &[Some(3)]).is_err());
+    }
+
+    #[test]
+    pub fn test_hyperslab_spans_contiguous() {
+        let slab = Hyperslab::new(vec![0, 0], vec![2, 3]);
+        let spans: Vec<Span> = slab.spans().collect();
+        assert_eq!(spans, vec![
+            Span { start: vec![0, 0], len: 3 },
+            Span { start: vec![1, 0], len: 3 },
+        ]);
+    }
+
+    #[test]
+    pub fn test_hyperslab_spans_strided() {
+        let slab = Hyperslab::with_stride_block(vec![0], vec![2], vec![3], vec![1]);
+        let spans: Vec<Span> = slab.spans().collect();
+        assert_eq!(spans, vec![
+            Span { start: vec![0], len: 1 },
+            Span { start: vec![2], len: 1 },
+            Span { start: vec![4], len: 1 },
+        ]);
     }
 }